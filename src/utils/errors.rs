@@ -1,83 +1,443 @@
 use actix::MailboxError;
-use actix_web::{error::ResponseError, HttpResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{error::ResponseError, HttpRequest, HttpResponse};
 use derive_more::Display;
 use diesel::r2d2::PoolError;
 use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use harsh::Error as HarshError;
 use jsonwebtoken::errors::{Error as JwtError, ErrorKind as JwtErrorKind};
+use serde::Serialize;
+use std::backtrace::Backtrace;
 use std::convert::From;
+use std::error::Error as StdError;
+
+/// Stable, machine-readable error codes.
+///
+/// Clients are expected to branch on these snake_case strings rather than
+/// parsing the human-readable `message`, so the set is treated as part of the
+/// public API and should only ever grow.
+#[derive(Debug, Clone, Copy)]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    InvalidToken,
+    InvalidIssuer,
+    RecordNotFound,
+    Conflict,
+    UniqueViolation,
+    ForeignKeyViolation,
+    NotNullViolation,
+    CheckViolation,
+    SerializationFailure,
+    UnprocessableEntity,
+    TooManyRequests,
+    Internal,
+}
+
+impl ErrorCode {
+    /// The wire representation carried in the `error` field of the body.
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::InvalidToken => "invalid_token",
+            ErrorCode::InvalidIssuer => "invalid_issuer",
+            ErrorCode::RecordNotFound => "record_not_found",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::UniqueViolation => "unique_violation",
+            ErrorCode::ForeignKeyViolation => "foreign_key_violation",
+            ErrorCode::NotNullViolation => "not_null_violation",
+            ErrorCode::CheckViolation => "check_violation",
+            ErrorCode::SerializationFailure => "serialization_failure",
+            ErrorCode::UnprocessableEntity => "unprocessable_entity",
+            ErrorCode::TooManyRequests => "too_many_requests",
+            ErrorCode::Internal => "internal_server_error",
+        }
+    }
+}
+
+/// The JSON body returned for every error, e.g.
+/// `{ "error": "unique_violation", "message": "...", "status": 409 }`.
+#[derive(Debug, Serialize)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+    message: &'a str,
+    status: u16,
+}
 
 #[derive(Debug, Display)]
 pub enum ServiceError {
     // 400
-    #[display(fmt = "BadRequest: {}", _0)]
-    BadRequest(String),
+    #[display(fmt = "BadRequest: {}", _1)]
+    BadRequest(ErrorCode, String),
 
     // 401
     #[display(fmt = "Unauthorized")]
     Unauthorized,
 
-    // 404
-    #[display(fmt = "Not Found: {}", _0)]
-    NotFound(String),
+    // 403
+    #[display(fmt = "Forbidden: {}", _0)]
+    Forbidden(String),
+
+    // 404 — carries the resource kind and identifier, so messages read
+    // "no such post found: 42" consistently across endpoints.
+    #[display(fmt = "no such {} found: {}", _0, _1)]
+    NotFound(String, String),
+
+    // 409
+    #[display(fmt = "Conflict: {}", _1)]
+    Conflict(ErrorCode, String),
+
+    // 422
+    #[display(fmt = "Unprocessable Entity: {}", _1)]
+    UnprocessableEntity(ErrorCode, String),
+
+    // 429
+    #[display(fmt = "Too Many Requests: {}", _0)]
+    TooManyRequests(String),
+
+    // 500+ — optionally keeps the original error as `source` and captures a
+    // backtrace so logs can show the full cause chain, while the client still
+    // only ever sees the sanitized `message`.
+    #[display(fmt = "Internal Server Error: {}", message)]
+    InternalServerError {
+        code: ErrorCode,
+        message: String,
+        source: Option<Box<dyn StdError + Send + Sync>>,
+        backtrace: Backtrace,
+    },
+}
+
+impl ServiceError {
+    /// A 400 with the generic `bad_request` code.
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ServiceError::BadRequest(ErrorCode::BadRequest, message.into())
+    }
+
+    /// A 404 describing which resource kind and identifier was missing,
+    /// e.g. `ServiceError::not_found("post", id)`.
+    pub fn not_found(entity: impl Into<String>, id: impl ToString) -> Self {
+        ServiceError::NotFound(entity.into(), id.to_string())
+    }
+
+    /// A 403 with the `forbidden` code.
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        ServiceError::Forbidden(message.into())
+    }
+
+    /// A 409 with the generic `conflict` code.
+    pub fn conflict(message: impl Into<String>) -> Self {
+        ServiceError::Conflict(ErrorCode::Conflict, message.into())
+    }
+
+    /// A 422 with the generic `unprocessable_entity` code.
+    pub fn unprocessable_entity(message: impl Into<String>) -> Self {
+        ServiceError::UnprocessableEntity(ErrorCode::UnprocessableEntity, message.into())
+    }
+
+    /// A 429 with the `too_many_requests` code.
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        ServiceError::TooManyRequests(message.into())
+    }
+
+    /// A 500 with the generic `internal_server_error` code and no preserved
+    /// cause.
+    pub fn internal(message: impl Into<String>) -> Self {
+        ServiceError::InternalServerError {
+            code: ErrorCode::Internal,
+            message: message.into(),
+            source: None,
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// A 500 that keeps the originating error as its [`source`] so the cause
+    /// chain survives into the logs.
+    ///
+    /// [`source`]: std::error::Error::source
+    pub fn internal_source<E>(message: impl Into<String>, source: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        ServiceError::InternalServerError {
+            code: ErrorCode::Internal,
+            message: message.into(),
+            source: Some(Box::new(source)),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Log the full `Display` chain and captured backtrace at error level.
+    /// Called for 500-class responses so operators see the real cause while
+    /// the client only receives the sanitized message.
+    fn log_server_error(&self) {
+        let mut chain = String::new();
+        let mut source = self.source();
+        while let Some(err) = source {
+            chain.push_str(&format!("\n  caused by: {}", err));
+            source = err.source();
+        }
+        if let ServiceError::InternalServerError { backtrace, .. } = self {
+            log::error!("{}{}\nbacktrace:\n{}", self, chain, backtrace);
+        } else {
+            log::error!("{}{}", self, chain);
+        }
+    }
+}
+
+impl StdError for ServiceError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ServiceError::InternalServerError {
+                source: Some(source),
+                ..
+            } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl ServiceError {
+    /// The HTTP status, machine-readable code and human message backing both
+    /// the JSON and HTML renderings. Keeping this in one place means the two
+    /// representations can never drift apart.
+    fn parts(&self) -> (StatusCode, ErrorCode, String) {
+        match *self {
+            ServiceError::BadRequest(code, ref message) => {
+                (StatusCode::BAD_REQUEST, code, message.clone())
+            }
+            ServiceError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "Unauthorized".into(),
+            ),
+            ServiceError::Forbidden(ref message) => {
+                (StatusCode::FORBIDDEN, ErrorCode::Forbidden, message.clone())
+            }
+            ServiceError::NotFound(..) => (
+                StatusCode::NOT_FOUND,
+                ErrorCode::RecordNotFound,
+                self.to_string(),
+            ),
+            ServiceError::Conflict(code, ref message) => {
+                (StatusCode::CONFLICT, code, message.clone())
+            }
+            ServiceError::UnprocessableEntity(code, ref message) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, code, message.clone())
+            }
+            ServiceError::TooManyRequests(ref message) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorCode::TooManyRequests,
+                message.clone(),
+            ),
+            ServiceError::InternalServerError {
+                code, ref message, ..
+            } => (StatusCode::INTERNAL_SERVER_ERROR, code, message.clone()),
+        }
+    }
+
+    /// Content-negotiated rendering: browsers (`Accept: text/html`) get a
+    /// plain HTML error page, while API clients keep the structured JSON body.
+    ///
+    /// This is **caller-opt-in**, not automatic. actix's [`ResponseError`]
+    /// trait is `fn error_response(&self) -> HttpResponse` — it receives no
+    /// request, so the `?`/`ResponseError` path can only ever emit JSON. To
+    /// serve HTML, a handler (or an error-handler middleware) that holds the
+    /// [`HttpRequest`] must call this explicitly, e.g.
+    /// `Err(err) => Ok(err.error_response_for(&req))`.
+    pub fn error_response_for(&self, req: &HttpRequest) -> HttpResponse {
+        if prefers_html(req) {
+            let (status, code, message) = self.parts();
+            if status.is_server_error() {
+                self.log_server_error();
+            }
+            HttpResponse::build(status)
+                .content_type("text/html; charset=utf-8")
+                .body(render_error_page(status, code, &message))
+        } else {
+            self.error_response()
+        }
+    }
+}
 
-    // 500+
-    #[display(fmt = "Internal Server Error: {}", _0)]
-    InternalServerError(String),
+/// Whether the client prefers an HTML response over JSON.
+fn prefers_html(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map(|accept| accept.contains("text/html"))
+        .unwrap_or(false)
+}
+
+/// Escape the five characters that are significant in HTML text/attribute
+/// context, so untrusted `message` content (path ids, diesel detail strings)
+/// can't break out into markup.
+fn html_escape(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for ch in input.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Minimal static template for the browser-facing error pages; one layout
+/// is shared by every status.
+///
+/// The title is derived from the status itself so every class this series
+/// introduced (403/409/422/429, …) is labeled correctly instead of collapsing
+/// into a generic 500.
+fn render_error_page(status: StatusCode, code: ErrorCode, message: &str) -> String {
+    let reason = status.canonical_reason().unwrap_or("Error");
+    let title = format!("{} {}", status.as_u16(), reason);
+    let message = html_escape(message);
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n<p>{message}</p>\n<p class=\"error-code\">{code}</p>\n</body>\n</html>\n",
+        code = code.as_str()
+    )
 }
 
 // impl ResponseError trait allows to convert errors into http responses with appropriate data
 impl ResponseError for ServiceError {
     fn error_response(&self) -> HttpResponse {
-        match *self {
-            ServiceError::InternalServerError(ref message) => {
-                HttpResponse::InternalServerError().json(message)
-            }
-            ServiceError::BadRequest(ref message) => HttpResponse::BadRequest().json(message),
-            ServiceError::Unauthorized => HttpResponse::Unauthorized().json("Unauthorized"),
-            ServiceError::NotFound(ref message) => HttpResponse::NotFound().json(message),
+        let (status, code, message) = self.parts();
+        if status.is_server_error() {
+            self.log_server_error();
         }
+        HttpResponse::build(status).json(ErrorResponse {
+            error: code.as_str(),
+            message: &message,
+            status: status.as_u16(),
+        })
     }
 }
 
-impl From<MailboxError> for ServiceError {
-    fn from(_error: MailboxError) -> Self {
-        ServiceError::InternalServerError("Mailbox".into())
+/// Attach a `BadRequest` context message to a fallible value.
+///
+/// Lets handlers write `some_option.ok_or_bad_request("post id missing")?`
+/// instead of the repetitive `.map_err(|_| ServiceError::BadRequest(..))?`.
+pub trait OkOrBadRequest<T> {
+    fn ok_or_bad_request(self, message: impl Into<String>) -> Result<T, ServiceError>;
+}
+
+/// Attach an `InternalServerError` context message to a fallible value.
+pub trait OkOrInternalError<T> {
+    fn ok_or_internal_error(self, message: impl Into<String>) -> Result<T, ServiceError>;
+}
+
+impl<T, E> OkOrBadRequest<T> for Result<T, E> {
+    fn ok_or_bad_request(self, message: impl Into<String>) -> Result<T, ServiceError> {
+        self.map_err(|_| ServiceError::bad_request(message))
     }
 }
 
+impl<T> OkOrBadRequest<T> for Option<T> {
+    fn ok_or_bad_request(self, message: impl Into<String>) -> Result<T, ServiceError> {
+        self.ok_or_else(|| ServiceError::bad_request(message))
+    }
+}
+
+impl<T, E> OkOrInternalError<T> for Result<T, E> {
+    fn ok_or_internal_error(self, message: impl Into<String>) -> Result<T, ServiceError> {
+        self.map_err(|_| ServiceError::internal(message))
+    }
+}
+
+impl<T> OkOrInternalError<T> for Option<T> {
+    fn ok_or_internal_error(self, message: impl Into<String>) -> Result<T, ServiceError> {
+        self.ok_or_else(|| ServiceError::internal(message))
+    }
+}
+
+/// Generate `From<$src>` impls that map an error into a 500 while preserving
+/// the original as the [`source`], so the per-type boilerplate stays a single
+/// line each.
+///
+/// [`source`]: std::error::Error::source
+macro_rules! internal_from {
+    ($($src:ty => $message:expr),+ $(,)?) => {
+        $(
+            impl From<$src> for ServiceError {
+                fn from(error: $src) -> Self {
+                    ServiceError::internal_source($message, error)
+                }
+            }
+        )+
+    };
+}
+
+internal_from! {
+    MailboxError => "Mailbox",
+    PoolError => "pool",
+}
+
 impl From<DieselError> for ServiceError {
     fn from(error: DieselError) -> ServiceError {
         // Right now we just care about UniqueViolation from diesel
         // But this would be helpful to easily map errors as our app grows
         match error {
             DieselError::DatabaseError(kind, info) => {
-                if let DatabaseErrorKind::UniqueViolation = kind {
-                    let msg = info.details().unwrap_or_else(|| info.message()).to_string();
-                    return ServiceError::BadRequest(msg);
+                // Prefer the driver's detail line, falling back to the bare message.
+                let detail = info.details().unwrap_or_else(|| info.message()).to_string();
+                match kind {
+                    DatabaseErrorKind::UniqueViolation => {
+                        ServiceError::Conflict(ErrorCode::UniqueViolation, detail)
+                    }
+                    DatabaseErrorKind::ForeignKeyViolation => {
+                        let msg = match info.constraint_name() {
+                            Some(constraint) => format!(
+                                "referenced record does not exist (constraint `{}`)",
+                                constraint
+                            ),
+                            None => detail,
+                        };
+                        ServiceError::Conflict(ErrorCode::ForeignKeyViolation, msg)
+                    }
+                    DatabaseErrorKind::NotNullViolation => {
+                        let msg = match info.column_name() {
+                            Some(column) => format!("column `{}` must not be null", column),
+                            None => detail,
+                        };
+                        ServiceError::UnprocessableEntity(ErrorCode::NotNullViolation, msg)
+                    }
+                    DatabaseErrorKind::CheckViolation => {
+                        let msg = match info.constraint_name() {
+                            Some(constraint) => format!("check constraint `{}` violated", constraint),
+                            None => detail,
+                        };
+                        ServiceError::UnprocessableEntity(ErrorCode::CheckViolation, msg)
+                    }
+                    DatabaseErrorKind::SerializationFailure => ServiceError::Conflict(
+                        ErrorCode::SerializationFailure,
+                        format!("{} (serialization failure, please retry)", detail),
+                    ),
+                    _ => ServiceError::internal("database"),
                 }
-                ServiceError::InternalServerError("database".into())
             }
-            DieselError::NotFound => {
-                ServiceError::NotFound("requested record was not found".into())
-            }
-            _ => ServiceError::InternalServerError("database".into()),
+            DieselError::NotFound => ServiceError::not_found("record", "unknown"),
+            other => ServiceError::internal_source("database", other),
         }
     }
 }
 
-impl From<PoolError> for ServiceError {
-    fn from(_error: PoolError) -> Self {
-        ServiceError::InternalServerError("pool".into())
-    }
-}
-
 // jwt
 impl From<JwtError> for ServiceError {
     fn from(error: JwtError) -> Self {
         match error.kind() {
-            JwtErrorKind::InvalidToken => ServiceError::BadRequest("Invalid Token".into()),
-            JwtErrorKind::InvalidIssuer => ServiceError::BadRequest("Invalid Issuer".into()),
+            JwtErrorKind::InvalidToken => {
+                ServiceError::BadRequest(ErrorCode::InvalidToken, "Invalid Token".into())
+            }
+            JwtErrorKind::InvalidIssuer => {
+                ServiceError::BadRequest(ErrorCode::InvalidIssuer, "Invalid Issuer".into())
+            }
             _ => ServiceError::Unauthorized,
         }
     }
@@ -86,15 +446,11 @@ impl From<JwtError> for ServiceError {
 impl From<HarshError> for ServiceError {
     fn from(error: HarshError) -> Self {
         match error {
-            HarshError::AlphabetLength => {
-                ServiceError::InternalServerError("harsh AlphabetLength error".into())
-            }
+            HarshError::AlphabetLength => ServiceError::internal("harsh AlphabetLength error"),
             HarshError::IllegalCharacter(_) => {
-                ServiceError::InternalServerError("harsh IllegalCharacter error".into())
-            }
-            HarshError::Separator => {
-                ServiceError::InternalServerError("harsh Separator error".into())
+                ServiceError::internal("harsh IllegalCharacter error")
             }
+            HarshError::Separator => ServiceError::internal("harsh Separator error"),
         }
     }
 }